@@ -7,6 +7,7 @@ pub type DbPool = diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<PgConnectio
 
 pub mod error;
 pub mod schema;
+pub mod users;
 
 pub async fn get_conn(
     pool: web::Data<DbPool>,
@@ -0,0 +1,33 @@
+diesel::table! {
+    users (id) {
+        id -> Int4,
+        #[max_length = 255]
+        login -> Varchar,
+        #[max_length = 255]
+        name -> Nullable<Varchar>,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    oauth_identities (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 32]
+        provider -> Varchar,
+        #[max_length = 255]
+        provider_user_id -> Varchar,
+        access_token -> Text,
+        refresh_token -> Nullable<Text>,
+        #[max_length = 255]
+        scopes -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::joinable!(oauth_identities -> users (user_id));
+diesel::allow_tables_to_appear_in_same_query!(users, oauth_identities);
@@ -0,0 +1,180 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use diesel::dsl::now;
+use diesel::prelude::*;
+
+use super::schema::{oauth_identities, users};
+use super::DbPool;
+use crate::error::ServiceError;
+use crate::oauth::NormalizedUser;
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = users)]
+pub struct User {
+    pub id: i32,
+    pub login: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = users)]
+struct UserFields<'a> {
+    login: &'a str,
+    name: Option<&'a str>,
+    email: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable, Associations)]
+#[diesel(table_name = oauth_identities, belongs_to(User))]
+pub struct OAuthIdentity {
+    pub id: i32,
+    pub user_id: i32,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub scopes: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = oauth_identities)]
+struct OAuthIdentityFields<'a> {
+    user_id: i32,
+    provider: &'a str,
+    provider_user_id: &'a str,
+    access_token: &'a str,
+    refresh_token: Option<&'a str>,
+    scopes: &'a str,
+}
+
+/// Fetch the stored access/refresh token for `user_id`'s identity with
+/// `provider`, so it can be revoked on logout.
+pub async fn find_tokens(
+    pool: web::Data<DbPool>,
+    user_id: i32,
+    provider: &'static str,
+) -> Result<Option<(String, Option<String>)>, ServiceError> {
+    let mut conn = super::get_conn(pool).await?;
+    Ok(web::block(move || {
+        oauth_identities::table
+            .filter(oauth_identities::user_id.eq(user_id))
+            .filter(oauth_identities::provider.eq(provider))
+            .select((oauth_identities::access_token, oauth_identities::refresh_token))
+            .first::<(String, Option<String>)>(&mut conn)
+            .optional()
+    })
+    .await??)
+}
+
+/// Delete `user_id`'s stored identity (and cached tokens) for `provider`,
+/// e.g. on logout.
+pub async fn delete_identity(
+    pool: web::Data<DbPool>,
+    user_id: i32,
+    provider: &'static str,
+) -> Result<(), ServiceError> {
+    let mut conn = super::get_conn(pool).await?;
+    web::block(move || {
+        diesel::delete(
+            oauth_identities::table
+                .filter(oauth_identities::user_id.eq(user_id))
+                .filter(oauth_identities::provider.eq(provider)),
+        )
+        .execute(&mut conn)
+    })
+    .await??;
+    Ok(())
+}
+
+/// Upsert `normalized` and its OAuth identity, returning the resulting local
+/// user. Repeat logins for the same `(provider, provider_user_id)` reuse the
+/// same local account instead of creating a new one each time.
+pub async fn upsert_from_oauth(
+    pool: web::Data<DbPool>,
+    normalized: &NormalizedUser,
+    access_token: &str,
+    refresh_token: Option<&str>,
+    scopes: &str,
+) -> Result<User, ServiceError> {
+    let provider = normalized.provider;
+    let provider_user_id = normalized.provider_user_id.clone();
+    let login = normalized.login.clone();
+    let name = normalized.name.clone();
+    let email = normalized.email.clone();
+    let access_token = access_token.to_string();
+    let refresh_token = refresh_token.map(str::to_string);
+    let scopes = scopes.to_string();
+
+    let mut conn = super::get_conn(pool).await?;
+    Ok(web::block(move || {
+        conn.transaction(|conn| {
+            let existing = oauth_identities::table
+                .inner_join(users::table)
+                .filter(oauth_identities::provider.eq(&provider))
+                .filter(oauth_identities::provider_user_id.eq(&provider_user_id))
+                .select((
+                    users::id,
+                    users::login,
+                    users::name,
+                    users::email,
+                    users::created_at,
+                    users::updated_at,
+                ))
+                .first::<User>(conn)
+                .optional()?;
+
+            let user = if let Some(user) = existing {
+                diesel::update(users::table.find(user.id))
+                    .set((
+                        UserFields {
+                            login: &login,
+                            name: name.as_deref(),
+                            email: email.as_deref(),
+                        },
+                        users::updated_at.eq(now),
+                    ))
+                    .get_result::<User>(conn)?
+            } else {
+                diesel::insert_into(users::table)
+                    .values(UserFields {
+                        login: &login,
+                        name: name.as_deref(),
+                        email: email.as_deref(),
+                    })
+                    .get_result::<User>(conn)?
+            };
+
+            diesel::insert_into(oauth_identities::table)
+                .values(OAuthIdentityFields {
+                    user_id: user.id,
+                    provider: &provider,
+                    provider_user_id: &provider_user_id,
+                    access_token: &access_token,
+                    refresh_token: refresh_token.as_deref(),
+                    scopes: &scopes,
+                })
+                .on_conflict((oauth_identities::provider, oauth_identities::provider_user_id))
+                .do_update()
+                .set((
+                    OAuthIdentityFields {
+                        user_id: user.id,
+                        provider: &provider,
+                        provider_user_id: &provider_user_id,
+                        access_token: &access_token,
+                        refresh_token: refresh_token.as_deref(),
+                        scopes: &scopes,
+                    },
+                    oauth_identities::updated_at.eq(now),
+                ))
+                .execute(conn)?;
+
+            diesel::QueryResult::Ok(user)
+        })
+    })
+    .await??)
+}
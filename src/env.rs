@@ -0,0 +1,6 @@
+use std::env;
+use once_cell::sync::Lazy;
+
+/// The externally reachable base URL of this service, used to build OAuth2 redirect URIs.
+pub static HOST_URL: Lazy<String> =
+    Lazy::new(|| env::var("HOST_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()));
@@ -0,0 +1,23 @@
+use actix_web::{HttpResponse, ResponseError};
+
+/// Top-level error type for failures that can occur while serving a request,
+/// independent of any particular subsystem (auth, database, ...).
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+    #[error("database pool error: {0}")]
+    Pool(#[from] diesel::r2d2::PoolError),
+    #[error("internal error")]
+    Blocking(#[from] actix_web::error::BlockingError),
+}
+
+impl ResponseError for ServiceError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ServiceError::Database(_) | ServiceError::Pool(_) | ServiceError::Blocking(_) => {
+                HttpResponse::InternalServerError().json("internal server error")
+            }
+        }
+    }
+}
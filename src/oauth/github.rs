@@ -13,40 +13,33 @@
 //! ...and follow the instructions.
 //!
 
-use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder, ResponseError};
-
-use oauth2::basic::{BasicClient, BasicErrorResponseType, BasicTokenType};
-use oauth2::{
-    Client, EmptyExtraTokenFields, PkceCodeChallenge, PkceCodeVerifier, RevocationErrorResponseType, StandardErrorResponse, StandardRevocableToken, StandardTokenIntrospectionResponse, StandardTokenResponse
-};
-use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope,
-    TokenResponse, TokenUrl,
-};
-use redis::{AsyncCommands as _, RedisError};
+use actix_web::web;
+
+use oauth2::basic::{BasicClient, BasicTokenType};
+use oauth2::{AuthUrl, ClientId, ClientSecret, RedirectUrl, Scope, TokenResponse, TokenUrl};
 use serde::{Deserialize, Serialize};
 use url::Url;
 
 use std::env;
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpListener;
 
+use super::{Error, NormalizedUser, OAuth2Provider, OAuthClient, OAuthToken};
 use crate::env::HOST_URL;
-use super::Error;
-
-type GitHubClient = Client<
-    StandardErrorResponse<BasicErrorResponseType>,
-    StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>,
-    BasicTokenType,
-    StandardTokenIntrospectionResponse<EmptyExtraTokenFields, BasicTokenType>,
-    StandardRevocableToken,
-    StandardErrorResponse<RevocationErrorResponseType>,
->;
-
-const GITHUB_CALLBACK_PATH: &str = "/auth/github/callback";
+
+const GITHUB_CALLBACK_PATH: &str = "/github/callback";
 const GITHUB_AUTH_URL: &str = "https://github.com/login/oauth/authorize";
 const GITHUB_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
 const GITHUB_USER_API_URL: &str = "https://api.github.com/user";
+const GITHUB_USER_EMAILS_API_URL: &str = "https://api.github.com/user/emails";
+
+/// One entry of `GET /user/emails`.
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubUserEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+    #[allow(dead_code)]
+    visibility: Option<String>,
+}
 
 /// Reference: https://github.com/XAMPPRocky/octocrab/blob/fae5b089161f6e97a7cd1eb7b4c7c6aa2589ee61/src/models.rs#L488-L512
 /// The simple profile for a GitHub user
@@ -78,6 +71,61 @@ pub struct GitHubUser {
     pub starred_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+/// [`OAuth2Provider`] implementation for GitHub's OAuth2 apps.
+pub struct GitHubProvider {
+    client: OAuthClient,
+}
+
+impl GitHubProvider {
+    /// GitHub has no RFC 7009 revocation endpoint: revoking a grant is
+    /// `DELETE /applications/{client_id}/grant` with HTTP Basic auth and a
+    /// JSON body, not the token-in-a-POST-body shape `oauth2::Client::
+    /// revoke_token` speaks. So no `set_revocation_uri` here; logout falls
+    /// back to deleting the local session only, same as any provider whose
+    /// revocation attempt fails.
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        let redirect_url = RedirectUrl::new(HOST_URL.to_string() + GITHUB_CALLBACK_PATH).unwrap();
+        let client = BasicClient::new(
+            ClientId::new(client_id),
+            Some(ClientSecret::new(client_secret)),
+            AuthUrl::new(GITHUB_AUTH_URL.to_string()).unwrap(),
+            Some(TokenUrl::new(GITHUB_TOKEN_URL.to_string()).unwrap()),
+        )
+        .set_redirect_uri(redirect_url);
+        Self { client }
+    }
+}
+
+impl OAuth2Provider for GitHubProvider {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn client(&self) -> &OAuthClient {
+        &self.client
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        vec![
+            Scope::new("public_repo".to_string()),
+            Scope::new("user:email".to_string()),
+        ]
+    }
+
+    async fn fetch_user(&self, token: &OAuthToken) -> Result<NormalizedUser, Error> {
+        let user = get_user_info_from_github(token).await?;
+        let email = get_primary_verified_email(token).await?;
+        Ok(NormalizedUser {
+            provider: self.name(),
+            provider_user_id: user.id.to_string(),
+            login: user.login,
+            name: user.name,
+            email: Some(email),
+            avatar_url: Some(user.avatar_url.to_string()),
+        })
+    }
+}
+
 pub fn github_config(cfg: &mut web::ServiceConfig) {
     let client_id = env::var("GITHUB_CLIENT_ID");
     let client_secret = env::var("GITHUB_CLIENT_SECRET");
@@ -85,65 +133,12 @@ pub fn github_config(cfg: &mut web::ServiceConfig) {
         log::info!("GITHUB environments are not set. Start without github auth");
         return;
     }
-    let redirect_url = RedirectUrl::new(HOST_URL.to_string() + GITHUB_CALLBACK_PATH).unwrap();
-    let client = BasicClient::new(
-        ClientId::new(client_id.unwrap()),
-        Some(ClientSecret::new(client_secret.unwrap())),
-        AuthUrl::new(GITHUB_AUTH_URL.to_string()).unwrap(),
-        Some(TokenUrl::new(GITHUB_TOKEN_URL.to_string()).unwrap()),
-    )
-    .set_redirect_uri(redirect_url);
-    cfg.service(
-        web::scope("/github")
-            .app_data(client)
-            .route("/auth", web::get().to(auth))
-            .route("/callback", web::get().to(callback))
-    );
-}
-
-async fn auth(github: web::Data<GitHubClient>, redis: web::Data<redis::aio::MultiplexedConnection>) -> impl Responder {
-    // Generate a PKCE challenge.
-    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
-    // Create an authorization URL to which we'll redirect the user.
-    let (authorize_url, csrf_state) = github
-        .authorize_url(CsrfToken::new_random)
-        .add_scope(Scope::new("public_repo".to_string()))
-        .add_scope(Scope::new("user:email".to_string()))
-        .set_pkce_challenge(pkce_challenge)
-        .url();
-    // Save the CSRF state to the Redis database.
-    let csrf_state = csrf_state.secret();
-    let pkce_verifier = pkce_verifier.secret();
-    let mut redis = (**redis).clone();
-    let _ = redis.set::<_ ,_ ,()>(csrf_state, pkce_verifier).await;
-    // Return the CSRF token to the client
-    HttpResponse::SeeOther()
-        .append_header(("Location", authorize_url.as_str()))
-        .append_header(("X-CSRF-Token", csrf_state.as_str()))
-        .finish()
-}
-
-async fn callback(
-    query: web::Query<super::CallbackQuery>,
-    github: web::Data<GitHubClient>, 
-    redis: web::Data<redis::aio::MultiplexedConnection>
-) -> Result<HttpResponse, Error> {
-    let query = query.into_inner();
-    let mut redis = (**redis).clone();
-    let pkce_verifier: String = redis.get(query.state.secret()).await?;
-    let pkce_verifier = PkceCodeVerifier::new(pkce_verifier);
-    let token = github.exchange_code(query.code)
-        .set_pkce_verifier(pkce_verifier)
-        .request_async(oauth2::reqwest::async_http_client)
-        .await?;
-
-    let user = get_user_info_from_github(&token).await?;
-
-    return Ok(HttpResponse::Ok().finish());
+    let provider = GitHubProvider::new(client_id.unwrap(), client_secret.unwrap());
+    super::oauth_config(cfg, provider);
 }
 
 async fn get_user_info_from_github(
-    token: &StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>
+    token: &OAuthToken
 ) -> Result<GitHubUser, Error> {
     // NB: Github returns a single comma-separated "scope" parameter instead of multiple
     // space-separated scopes. Github-specific clients can parse this scope into
@@ -165,6 +160,7 @@ async fn get_user_info_from_github(
             reqwest::Client::new()
                 .get(GITHUB_USER_API_URL)
                 .header("Authorization", format!("Bearer {}", token.access_token().secret()))
+                .header("User-Agent", "LibreUser")
                 .send()
                 .await
                 .map_err(|_| Error::Other("Failed to get user info from Github"))?
@@ -193,4 +189,35 @@ async fn get_user_info_from_github(
     log::debug!("Github return info: {:?}\n", user_info);
 
     Ok(user_info)
-}
\ No newline at end of file
+}
+
+/// GitHub's `/user` endpoint frequently returns `email: null` even when the
+/// `user:email` scope was granted, so the primary verified address has to be
+/// fetched separately from `/user/emails`. Login is rejected unless the
+/// account's primary email is verified; other verified addresses are not used.
+async fn get_primary_verified_email(token: &OAuthToken) -> Result<String, Error> {
+    let response = reqwest::Client::new()
+        .get(GITHUB_USER_EMAILS_API_URL)
+        .header("Authorization", format!("Bearer {}", token.access_token().secret()))
+        .header("User-Agent", "LibreUser")
+        .send()
+        .await
+        .map_err(|_| Error::Other("Failed to get user emails from Github"))?;
+
+    match response.status() {
+        reqwest::StatusCode::OK => {}
+        reqwest::StatusCode::UNAUTHORIZED => return Err(Error::Authentication),
+        _ => return Err(Error::Other("Failed to get user emails from Github")),
+    }
+
+    let emails = response
+        .json::<Vec<GitHubUserEmail>>()
+        .await
+        .map_err(|_| Error::Other("Failed to parse user emails from Github"))?;
+
+    emails
+        .into_iter()
+        .find(|e| e.primary && e.verified)
+        .map(|e| e.email)
+        .ok_or(Error::Other("Github account has no verified primary email"))
+}
@@ -0,0 +1,111 @@
+//! [`OAuth2Provider`] implementation for GitLab's OAuth2 apps.
+//!
+//! Mirrors [`super::github`]: GitLab's authorize/token endpoints are fixed
+//! (self-managed GitLab instances are out of scope for now), and the
+//! `read_user` scope is enough to read the profile from `/api/v4/user`.
+
+use actix_web::web;
+
+use oauth2::basic::{BasicClient, BasicTokenType};
+use oauth2::{
+    AuthUrl, ClientId, ClientSecret, RedirectUrl, RevocationUrl, Scope, TokenResponse, TokenUrl,
+};
+use serde::Deserialize;
+
+use std::env;
+
+use super::{Error, NormalizedUser, OAuth2Provider, OAuthClient, OAuthToken};
+use crate::env::HOST_URL;
+
+const GITLAB_CALLBACK_PATH: &str = "/gitlab/callback";
+const GITLAB_AUTH_URL: &str = "https://gitlab.com/oauth/authorize";
+const GITLAB_TOKEN_URL: &str = "https://gitlab.com/oauth/token";
+const GITLAB_REVOKE_URL: &str = "https://gitlab.com/oauth/revoke";
+const GITLAB_USER_API_URL: &str = "https://gitlab.com/api/v4/user";
+
+/// The subset of GitLab's `GET /api/v4/user` response we care about.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitLabUser {
+    pub id: u64,
+    pub username: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+pub struct GitLabProvider {
+    client: OAuthClient,
+}
+
+impl GitLabProvider {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        let redirect_url = RedirectUrl::new(HOST_URL.to_string() + GITLAB_CALLBACK_PATH).unwrap();
+        let client = BasicClient::new(
+            ClientId::new(client_id),
+            Some(ClientSecret::new(client_secret)),
+            AuthUrl::new(GITLAB_AUTH_URL.to_string()).unwrap(),
+            Some(TokenUrl::new(GITLAB_TOKEN_URL.to_string()).unwrap()),
+        )
+        .set_redirect_uri(redirect_url)
+        .set_revocation_uri(RevocationUrl::new(GITLAB_REVOKE_URL.to_string()).unwrap());
+        Self { client }
+    }
+}
+
+impl OAuth2Provider for GitLabProvider {
+    fn name(&self) -> &'static str {
+        "gitlab"
+    }
+
+    fn client(&self) -> &OAuthClient {
+        &self.client
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        vec![Scope::new("read_user".to_string())]
+    }
+
+    async fn fetch_user(&self, token: &OAuthToken) -> Result<NormalizedUser, Error> {
+        if !matches!(token.token_type(), BasicTokenType::Bearer) {
+            return Err(Error::Other("Unsupported token type"));
+        }
+
+        let response = reqwest::Client::new()
+            .get(GITLAB_USER_API_URL)
+            .header("Authorization", format!("Bearer {}", token.access_token().secret()))
+            .send()
+            .await
+            .map_err(|_| Error::Other("Failed to get user info from GitLab"))?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => {}
+            reqwest::StatusCode::UNAUTHORIZED => return Err(Error::Authentication),
+            _ => return Err(Error::Other("Failed to get user info from GitLab")),
+        }
+
+        let user = response
+            .json::<GitLabUser>()
+            .await
+            .map_err(|_| Error::Other("Failed to parse user info from GitLab"))?;
+
+        Ok(NormalizedUser {
+            provider: self.name(),
+            provider_user_id: user.id.to_string(),
+            login: user.username,
+            name: user.name,
+            email: user.email,
+            avatar_url: user.avatar_url,
+        })
+    }
+}
+
+pub fn gitlab_config(cfg: &mut web::ServiceConfig) {
+    let client_id = env::var("GITLAB_CLIENT_ID");
+    let client_secret = env::var("GITLAB_CLIENT_SECRET");
+    if client_id.is_err() || client_secret.is_err() {
+        log::info!("GITLAB environments are not set. Start without gitlab auth");
+        return;
+    }
+    let provider = GitLabProvider::new(client_id.unwrap(), client_secret.unwrap());
+    super::oauth_config(cfg, provider);
+}
@@ -0,0 +1,114 @@
+//! [`OAuth2Provider`] implementation for Google's OAuth2 apps.
+//!
+//! Uses Google's well-known authorization/token endpoints and the OIDC
+//! userinfo endpoint with the `openid email profile` scopes.
+
+use actix_web::web;
+
+use oauth2::basic::{BasicClient, BasicTokenType};
+use oauth2::{
+    AuthUrl, ClientId, ClientSecret, RedirectUrl, RevocationUrl, Scope, TokenResponse, TokenUrl,
+};
+use serde::Deserialize;
+
+use std::env;
+
+use super::{Error, NormalizedUser, OAuth2Provider, OAuthClient, OAuthToken};
+use crate::env::HOST_URL;
+
+const GOOGLE_CALLBACK_PATH: &str = "/google/callback";
+const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const GOOGLE_REVOKE_URL: &str = "https://oauth2.googleapis.com/revoke";
+const GOOGLE_USERINFO_URL: &str = "https://openidconnect.googleapis.com/v1/userinfo";
+
+/// The subset of Google's OIDC userinfo response we care about.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GoogleUser {
+    pub sub: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub picture: Option<String>,
+}
+
+pub struct GoogleProvider {
+    client: OAuthClient,
+}
+
+impl GoogleProvider {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        let redirect_url = RedirectUrl::new(HOST_URL.to_string() + GOOGLE_CALLBACK_PATH).unwrap();
+        let client = BasicClient::new(
+            ClientId::new(client_id),
+            Some(ClientSecret::new(client_secret)),
+            AuthUrl::new(GOOGLE_AUTH_URL.to_string()).unwrap(),
+            Some(TokenUrl::new(GOOGLE_TOKEN_URL.to_string()).unwrap()),
+        )
+        .set_redirect_uri(redirect_url)
+        .set_revocation_uri(RevocationUrl::new(GOOGLE_REVOKE_URL.to_string()).unwrap());
+        Self { client }
+    }
+}
+
+impl OAuth2Provider for GoogleProvider {
+    fn name(&self) -> &'static str {
+        "google"
+    }
+
+    fn client(&self) -> &OAuthClient {
+        &self.client
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        vec![
+            Scope::new("openid".to_string()),
+            Scope::new("email".to_string()),
+            Scope::new("profile".to_string()),
+        ]
+    }
+
+    async fn fetch_user(&self, token: &OAuthToken) -> Result<NormalizedUser, Error> {
+        if !matches!(token.token_type(), BasicTokenType::Bearer) {
+            return Err(Error::Other("Unsupported token type"));
+        }
+
+        let response = reqwest::Client::new()
+            .get(GOOGLE_USERINFO_URL)
+            .header("Authorization", format!("Bearer {}", token.access_token().secret()))
+            .send()
+            .await
+            .map_err(|_| Error::Other("Failed to get user info from Google"))?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => {}
+            reqwest::StatusCode::UNAUTHORIZED => return Err(Error::Authentication),
+            _ => return Err(Error::Other("Failed to get user info from Google")),
+        }
+
+        let user = response
+            .json::<GoogleUser>()
+            .await
+            .map_err(|_| Error::Other("Failed to parse user info from Google"))?;
+
+        Ok(NormalizedUser {
+            provider: self.name(),
+            provider_user_id: user.sub,
+            login: user.email.clone().unwrap_or_default(),
+            name: user.name,
+            email: user.email,
+            avatar_url: user.picture,
+        })
+    }
+}
+
+pub fn google_config(cfg: &mut web::ServiceConfig) {
+    let client_id = env::var("GOOGLE_CLIENT_ID");
+    let client_secret = env::var("GOOGLE_CLIENT_SECRET");
+    if client_id.is_err() || client_secret.is_err() {
+        log::info!("GOOGLE environments are not set. Start without google auth");
+        return;
+    }
+    let provider = GoogleProvider::new(client_id.unwrap(), client_secret.unwrap());
+    super::oauth_config(cfg, provider);
+}
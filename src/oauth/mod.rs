@@ -0,0 +1,215 @@
+//! OAuth2 identity providers.
+//!
+//! Each supported identity provider (GitHub, GitLab, Google, ...) implements
+//! [`OAuth2Provider`], and [`oauth_config`] mounts the same `/auth` + `/callback`
+//! pair for any of them under a scope named after the provider, e.g.
+//! `/github/auth`, `/gitlab/callback`. This lets operators enable several
+//! providers from one binary instead of hardcoding a single flow.
+
+pub mod github;
+pub mod gitlab;
+pub mod google;
+pub mod oidc;
+
+use actix_web::{web, HttpResponse, Responder, ResponseError};
+use oauth2::basic::{BasicClient, BasicErrorResponseType, BasicTokenType};
+use oauth2::{
+    AccessToken, AuthorizationCode, Client, CsrfToken, EmptyExtraTokenFields, PkceCodeChallenge,
+    PkceCodeVerifier, RefreshToken, RevocationErrorResponseType, Scope, StandardErrorResponse,
+    StandardRevocableToken, StandardTokenIntrospectionResponse, StandardTokenResponse,
+    TokenResponse,
+};
+use redis::AsyncCommands as _;
+use serde::Deserialize;
+
+/// The concrete `oauth2::Client` instantiation shared by every provider.
+pub(crate) type OAuthClient = Client<
+    StandardErrorResponse<BasicErrorResponseType>,
+    StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>,
+    BasicTokenType,
+    StandardTokenIntrospectionResponse<EmptyExtraTokenFields, BasicTokenType>,
+    StandardRevocableToken,
+    StandardErrorResponse<RevocationErrorResponseType>,
+>;
+
+pub(crate) type OAuthToken = StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>;
+
+/// How long a CSRF state / PKCE verifier pair may sit in Redis before it's
+/// considered stale and rejected.
+pub(crate) const OAUTH_STATE_TTL_SECONDS: u64 = 600;
+
+/// A user profile normalized across identity providers, used to create or
+/// look up a local account once the OAuth2 dance completes.
+#[derive(Debug, Clone)]
+pub struct NormalizedUser {
+    pub provider: &'static str,
+    pub provider_user_id: String,
+    pub login: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+/// A single identity provider able to drive the OAuth2 authorization-code flow.
+pub trait OAuth2Provider: Send + Sync + 'static {
+    /// Short provider name, used to scope routes (`/{name}/auth`) and as the
+    /// `provider` column value when the identity is persisted.
+    fn name(&self) -> &'static str;
+    fn client(&self) -> &OAuthClient;
+    fn scopes(&self) -> Vec<Scope>;
+    // Every call site is generic over `P: OAuth2Provider` (never a `dyn`),
+    // so the missing auto-trait bounds async-fn-in-trait warns about don't
+    // apply here.
+    #[allow(async_fn_in_trait)]
+    async fn fetch_user(&self, token: &OAuthToken) -> Result<NormalizedUser, Error>;
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: AuthorizationCode,
+    pub state: CsrfToken,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("authentication failed")]
+    Authentication,
+    #[error("{0}")]
+    Other(&'static str),
+    #[error("redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+}
+
+impl ResponseError for Error {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Error::Authentication => HttpResponse::Unauthorized().finish(),
+            Error::Other(_) | Error::Redis(_) => HttpResponse::InternalServerError().finish(),
+        }
+    }
+}
+
+/// Mount `/auth` and `/callback` for `provider` under a scope named after it,
+/// e.g. `/github/auth`, `/gitlab/callback`.
+pub fn oauth_config<P: OAuth2Provider>(cfg: &mut web::ServiceConfig, provider: P) {
+    cfg.service(
+        web::scope(&format!("/{}", provider.name()))
+            .app_data(web::Data::new(provider))
+            .route("/auth", web::get().to(auth::<P>))
+            .route("/callback", web::get().to(callback::<P>))
+            .route("/logout", web::post().to(logout::<P>)),
+    );
+}
+
+/// Revoke `revocable` (an access or refresh token) at `provider`.
+async fn revoke_token<P: OAuth2Provider>(
+    provider: &P,
+    revocable: StandardRevocableToken,
+) -> Result<(), Error> {
+    provider
+        .client()
+        .revoke_token(revocable)
+        .map_err(|_| Error::Other("Provider does not support token revocation"))?
+        .request_async(oauth2::reqwest::async_http_client)
+        .await
+        .map_err(|_| Error::Other("Failed to revoke token at provider"))?;
+    Ok(())
+}
+
+async fn auth<P: OAuth2Provider>(
+    provider: web::Data<P>,
+    redis: web::Data<redis::aio::MultiplexedConnection>,
+) -> impl Responder {
+    // Generate a PKCE challenge.
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    // Create an authorization URL to which we'll redirect the user.
+    let mut authorize_request = provider.client().authorize_url(CsrfToken::new_random);
+    for scope in provider.scopes() {
+        authorize_request = authorize_request.add_scope(scope);
+    }
+    let (authorize_url, csrf_state) = authorize_request.set_pkce_challenge(pkce_challenge).url();
+    // Save the CSRF state to the Redis database.
+    let csrf_state = csrf_state.secret();
+    let pkce_verifier = pkce_verifier.secret();
+    let mut redis = (**redis).clone();
+    let _ = redis
+        .set_ex::<_, _, ()>(csrf_state, pkce_verifier, OAUTH_STATE_TTL_SECONDS)
+        .await;
+    // Return the CSRF token to the client
+    HttpResponse::SeeOther()
+        .append_header(("Location", authorize_url.as_str()))
+        .append_header(("X-CSRF-Token", csrf_state.as_str()))
+        .finish()
+}
+
+async fn callback<P: OAuth2Provider>(
+    query: web::Query<CallbackQuery>,
+    provider: web::Data<P>,
+    redis: web::Data<redis::aio::MultiplexedConnection>,
+    db: web::Data<crate::database::DbPool>,
+) -> Result<HttpResponse, Error> {
+    let query = query.into_inner();
+    let mut redis = (**redis).clone();
+    // Atomically fetch and remove the stored verifier so each CSRF state can
+    // only ever be redeemed once; a missing/expired key means the state
+    // wasn't one we issued (or it was already used), not a valid callback.
+    let pkce_verifier: Option<String> = redis.get_del(query.state.secret()).await?;
+    let pkce_verifier = pkce_verifier.ok_or(Error::Authentication)?;
+    let pkce_verifier = PkceCodeVerifier::new(pkce_verifier);
+    let token = provider
+        .client()
+        .exchange_code(query.code)
+        .set_pkce_verifier(pkce_verifier)
+        .request_async(oauth2::reqwest::async_http_client)
+        .await
+        .map_err(|_| Error::Other("Failed to exchange authorization code"))?;
+
+    let user = provider.fetch_user(&token).await?;
+    let granted_scopes = token
+        .scopes()
+        .map(|scopes| scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+
+    let local_user = crate::database::users::upsert_from_oauth(
+        db,
+        &user,
+        token.access_token().secret(),
+        token.refresh_token().map(|t| t.secret().as_str()),
+        &granted_scopes,
+    )
+    .await
+    .map_err(|_| Error::Other("Failed to persist user"))?;
+
+    let session_token = crate::session::issue_token(local_user.id)
+        .map_err(|_| Error::Authentication)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "token": session_token })))
+}
+
+/// Revoke the session's stored `provider` token (preferring the refresh
+/// token, which GitLab/Google revoke together with any access tokens derived
+/// from it) and drop the cached identity so a future login re-authorizes.
+async fn logout<P: OAuth2Provider>(
+    user: crate::session::AuthenticatedUser,
+    provider: web::Data<P>,
+    db: web::Data<crate::database::DbPool>,
+) -> Result<HttpResponse, Error> {
+    if let Some((access_token, refresh_token)) =
+        crate::database::users::find_tokens(db.clone(), user.user_id, provider.name())
+            .await
+            .map_err(|_| Error::Other("Failed to load stored tokens"))?
+    {
+        let revocable = match refresh_token {
+            Some(refresh_token) => StandardRevocableToken::RefreshToken(RefreshToken::new(refresh_token)),
+            None => StandardRevocableToken::AccessToken(AccessToken::new(access_token)),
+        };
+        // Best-effort: the provider may already consider the token invalid.
+        let _ = revoke_token(provider.get_ref(), revocable).await;
+    }
+
+    crate::database::users::delete_identity(db, user.user_id, provider.name())
+        .await
+        .map_err(|_| Error::Other("Failed to delete local session"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,359 @@
+//! Generic OpenID Connect provider, for SSO providers (Keycloak, Auth0,
+//! Google, ...) that expose a `/.well-known/openid-configuration` discovery
+//! document instead of provider-specific userinfo scraping.
+//!
+//! Unlike [`OAuth2Provider`](super::OAuth2Provider), an OIDC login is proven
+//! by the `id_token` returned alongside the access token, so the flow here
+//! also has to carry a per-login `nonce` through Redis and validate the
+//! token's signature against the provider's JWKS before trusting its claims.
+
+use actix_web::{web, HttpResponse, Responder};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use oauth2::basic::{BasicClient, BasicErrorResponseType, BasicTokenType};
+use oauth2::{
+    AuthUrl, Client, ClientId, ClientSecret, CsrfToken, EmptyExtraTokenFields, ExtraTokenFields,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RevocationErrorResponseType, RevocationUrl,
+    Scope, StandardErrorResponse, StandardRevocableToken, StandardTokenIntrospectionResponse,
+    StandardTokenResponse, TokenResponse, TokenUrl,
+};
+use redis::AsyncCommands as _;
+use serde::{Deserialize, Serialize};
+
+use std::env;
+
+use super::{CallbackQuery, Error, NormalizedUser};
+
+/// The extra field OIDC adds on top of a plain OAuth2 token response.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IdTokenField {
+    pub id_token: Option<String>,
+}
+impl ExtraTokenFields for IdTokenField {}
+
+pub(crate) type OidcClient = Client<
+    StandardErrorResponse<BasicErrorResponseType>,
+    StandardTokenResponse<IdTokenField, BasicTokenType>,
+    BasicTokenType,
+    StandardTokenIntrospectionResponse<EmptyExtraTokenFields, BasicTokenType>,
+    StandardRevocableToken,
+    StandardErrorResponse<RevocationErrorResponseType>,
+>;
+pub(crate) type OidcToken = StandardTokenResponse<IdTokenField, BasicTokenType>;
+
+/// The subset of `/.well-known/openid-configuration` we need.
+#[derive(Debug, Clone, Deserialize)]
+struct OidcDiscoveryDocument {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    revocation_endpoint: Option<String>,
+}
+
+/// The `aud` claim is a single string for most providers (Google always;
+/// Keycloak/Auth0 with one audience configured) but RFC 7519 also allows an
+/// array of strings, so accept either shape.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, value: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == value,
+            Audience::Many(auds) => auds.iter().any(|a| a == value),
+        }
+    }
+}
+
+/// The claims we trust out of a validated ID token.
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    aud: Audience,
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    name: Option<String>,
+}
+
+/// Fetch and parse `{issuer_url}/.well-known/openid-configuration`.
+async fn discover(issuer_url: &str) -> Result<OidcDiscoveryDocument, Error> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer_url.trim_end_matches('/')
+    );
+    reqwest::get(&url)
+        .await
+        .map_err(|_| Error::Other("Failed to fetch OIDC discovery document"))?
+        .json::<OidcDiscoveryDocument>()
+        .await
+        .map_err(|_| Error::Other("Failed to parse OIDC discovery document"))
+}
+
+/// An OIDC-speaking identity provider, built from its issuer's discovery
+/// document rather than hardcoded endpoints.
+pub struct OidcProvider {
+    name: &'static str,
+    issuer: String,
+    client_id: String,
+    jwks_uri: String,
+    client: OidcClient,
+}
+
+impl OidcProvider {
+    /// Discover `issuer_url`'s endpoints and build a provider mounted at
+    /// `/{name}/auth` and `/{name}/callback`.
+    pub async fn discover(
+        name: &'static str,
+        issuer_url: &str,
+        client_id: String,
+        client_secret: String,
+    ) -> Result<Self, Error> {
+        let doc = discover(issuer_url).await?;
+        let redirect_url =
+            RedirectUrl::new(format!("{}/{name}/callback", *crate::env::HOST_URL)).unwrap();
+        let mut client = BasicClient::new(
+            ClientId::new(client_id.clone()),
+            Some(ClientSecret::new(client_secret)),
+            AuthUrl::new(doc.authorization_endpoint).unwrap(),
+            Some(TokenUrl::new(doc.token_endpoint).unwrap()),
+        )
+        .set_redirect_uri(redirect_url);
+        // Not every discovery document advertises one (and some that do use
+        // a shape this client can't drive); when it's missing, logout still
+        // clears the local session, it just can't reach the provider too.
+        if let Some(revocation_endpoint) = doc.revocation_endpoint {
+            if let Ok(revocation_uri) = RevocationUrl::new(revocation_endpoint) {
+                client = client.set_revocation_uri(revocation_uri);
+            }
+        }
+        Ok(Self {
+            name,
+            issuer: issuer_url.trim_end_matches('/').to_string(),
+            client_id,
+            jwks_uri: doc.jwks_uri,
+            client,
+        })
+    }
+
+    /// Validate `id_token`'s signature against this provider's JWKS, then its
+    /// `iss`/`aud`/`exp`/`nonce` claims, and return the normalized user.
+    async fn validate_id_token(&self, id_token: &str, expected_nonce: &str) -> Result<NormalizedUser, Error> {
+        let header = jsonwebtoken::decode_header(id_token)
+            .map_err(|_| Error::Other("Malformed ID token"))?;
+        let kid = header.kid.ok_or(Error::Other("ID token is missing a key id"))?;
+
+        let jwks: JwkSet = reqwest::get(&self.jwks_uri)
+            .await
+            .map_err(|_| Error::Other("Failed to fetch JWKS"))?
+            .json()
+            .await
+            .map_err(|_| Error::Other("Failed to parse JWKS"))?;
+        let jwk = jwks.find(&kid).ok_or(Error::Other("No matching JWKS key"))?;
+        let decoding_key =
+            DecodingKey::from_jwk(jwk).map_err(|_| Error::Other("Invalid JWKS key"))?;
+
+        // Check the attacker-controlled `alg` header against our own allowlist
+        // instead of trusting it, which would otherwise let a forged token
+        // pick e.g. `none` or downgrade to a weaker algorithm. Validation then
+        // uses that single algorithm: jsonwebtoken rejects a `Validation`
+        // listing any algorithm of a different family than the key.
+        if !matches!(header.alg, Algorithm::RS256 | Algorithm::ES256) {
+            return Err(Error::Authentication);
+        }
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_issuer(&[&self.issuer]);
+
+        let data = decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+            .map_err(|_| Error::Authentication)?;
+        let claims = data.claims;
+
+        if claims.iss != self.issuer || !claims.aud.contains(&self.client_id) {
+            return Err(Error::Authentication);
+        }
+        if claims.nonce.as_deref() != Some(expected_nonce) {
+            return Err(Error::Authentication);
+        }
+
+        Ok(NormalizedUser {
+            provider: self.name,
+            provider_user_id: claims.sub,
+            login: claims.email.clone().unwrap_or_default(),
+            name: claims.name,
+            email: claims.email,
+            avatar_url: None,
+        })
+    }
+}
+
+/// State stashed in Redis between `/auth` and `/callback`: the PKCE verifier
+/// and the nonce the ID token must echo back.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLogin {
+    pkce_verifier: String,
+    nonce: String,
+}
+
+/// Discover and build the Keycloak provider from `KEYCLOAK_ISSUER_URL`,
+/// `KEYCLOAK_CLIENT_ID` and `KEYCLOAK_CLIENT_SECRET`, if all three are set.
+/// Unlike the plain OAuth2 `*_config` functions this is async, since
+/// discovery requires a network round-trip; call it once at startup and pass
+/// the result to [`oidc_config`].
+pub async fn keycloak_provider() -> Option<OidcProvider> {
+    let issuer = env::var("KEYCLOAK_ISSUER_URL").ok()?;
+    let client_id = env::var("KEYCLOAK_CLIENT_ID").ok()?;
+    let client_secret = env::var("KEYCLOAK_CLIENT_SECRET").ok()?;
+    match OidcProvider::discover("keycloak", &issuer, client_id, client_secret).await {
+        Ok(provider) => Some(provider),
+        Err(err) => {
+            log::error!("Failed to discover Keycloak OIDC configuration: {err}");
+            None
+        }
+    }
+}
+
+/// Discover and build the Auth0 provider from `AUTH0_ISSUER_URL`,
+/// `AUTH0_CLIENT_ID` and `AUTH0_CLIENT_SECRET`, if all three are set.
+pub async fn auth0_provider() -> Option<OidcProvider> {
+    let issuer = env::var("AUTH0_ISSUER_URL").ok()?;
+    let client_id = env::var("AUTH0_CLIENT_ID").ok()?;
+    let client_secret = env::var("AUTH0_CLIENT_SECRET").ok()?;
+    match OidcProvider::discover("auth0", &issuer, client_id, client_secret).await {
+        Ok(provider) => Some(provider),
+        Err(err) => {
+            log::error!("Failed to discover Auth0 OIDC configuration: {err}");
+            None
+        }
+    }
+}
+
+/// Mount `/auth` and `/callback` for an OIDC `provider`, under `/{name}`.
+pub fn oidc_config(cfg: &mut web::ServiceConfig, provider: OidcProvider) {
+    let name = provider.name;
+    cfg.service(
+        web::scope(&format!("/{name}"))
+            .app_data(web::Data::new(provider))
+            .route("/auth", web::get().to(auth))
+            .route("/callback", web::get().to(callback))
+            .route("/logout", web::post().to(logout)),
+    );
+}
+
+async fn logout(
+    user: crate::session::AuthenticatedUser,
+    provider: web::Data<OidcProvider>,
+    db: web::Data<crate::database::DbPool>,
+) -> Result<HttpResponse, Error> {
+    if let Some((access_token, refresh_token)) =
+        crate::database::users::find_tokens(db.clone(), user.user_id, provider.name)
+            .await
+            .map_err(|_| Error::Other("Failed to load stored tokens"))?
+    {
+        let revocable = match refresh_token {
+            Some(refresh_token) => {
+                oauth2::StandardRevocableToken::RefreshToken(oauth2::RefreshToken::new(refresh_token))
+            }
+            None => oauth2::StandardRevocableToken::AccessToken(oauth2::AccessToken::new(access_token)),
+        };
+        // Best-effort, same as the generic `oauth::logout` path: a provider
+        // without a revocation endpoint configured fails here with a
+        // configuration error, but that shouldn't stop the local session
+        // below from being cleared.
+        if let Ok(request) = provider.client.revoke_token(revocable) {
+            let _ = request.request_async(oauth2::reqwest::async_http_client).await;
+        }
+    }
+
+    crate::database::users::delete_identity(db, user.user_id, provider.name)
+        .await
+        .map_err(|_| Error::Other("Failed to delete local session"))?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn auth(
+    provider: web::Data<OidcProvider>,
+    redis: web::Data<redis::aio::MultiplexedConnection>,
+) -> impl Responder {
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    // `oauth2` (unlike `openidconnect`) has no first-class `Nonce` type, so
+    // borrow its CSRF token generator for an equally random string instead.
+    let nonce = CsrfToken::new_random().secret().clone();
+    let (authorize_url, csrf_state) = provider
+        .client
+        .authorize_url(CsrfToken::new_random)
+        .add_scope(Scope::new("openid".to_string()))
+        .add_scope(Scope::new("email".to_string()))
+        .add_scope(Scope::new("profile".to_string()))
+        .set_pkce_challenge(pkce_challenge)
+        .add_extra_param("nonce", nonce.clone())
+        .url();
+
+    let pending = PendingLogin {
+        pkce_verifier: pkce_verifier.secret().clone(),
+        nonce,
+    };
+    let mut redis = (**redis).clone();
+    let _ = redis
+        .set_ex::<_, _, ()>(
+            csrf_state.secret(),
+            serde_json::to_string(&pending).unwrap(),
+            super::OAUTH_STATE_TTL_SECONDS,
+        )
+        .await;
+
+    HttpResponse::SeeOther()
+        .append_header(("Location", authorize_url.as_str()))
+        .finish()
+}
+
+async fn callback(
+    query: web::Query<CallbackQuery>,
+    provider: web::Data<OidcProvider>,
+    redis: web::Data<redis::aio::MultiplexedConnection>,
+    db: web::Data<crate::database::DbPool>,
+) -> Result<HttpResponse, Error> {
+    let query = query.into_inner();
+    let mut redis = (**redis).clone();
+    let pending: Option<String> = redis.get_del(query.state.secret()).await?;
+    let pending: PendingLogin = pending
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .ok_or(Error::Authentication)?;
+
+    let token: OidcToken = provider
+        .client
+        .exchange_code(query.code)
+        .set_pkce_verifier(PkceCodeVerifier::new(pending.pkce_verifier))
+        .request_async(oauth2::reqwest::async_http_client)
+        .await
+        .map_err(|_| Error::Other("Failed to exchange authorization code"))?;
+
+    let id_token = token
+        .extra_fields()
+        .id_token
+        .as_deref()
+        .ok_or(Error::Other("Provider did not return an ID token"))?;
+
+    let user = provider.validate_id_token(id_token, &pending.nonce).await?;
+
+    let local_user = crate::database::users::upsert_from_oauth(
+        db,
+        &user,
+        token.access_token().secret(),
+        token.refresh_token().map(|t| t.secret().as_str()),
+        "openid email profile",
+    )
+    .await
+    .map_err(|_| Error::Other("Failed to persist user"))?;
+
+    let session_token =
+        crate::session::issue_token(local_user.id).map_err(|_| Error::Authentication)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "token": session_token })))
+}
@@ -0,0 +1,114 @@
+//! Session tokens issued after a successful OAuth2 login.
+//!
+//! A local user, created or looked up via [`crate::database::users`], is
+//! turned into a signed JWT (HS256) that the client presents on subsequent
+//! requests as `Authorization: Bearer <jwt>`. [`AuthenticatedUser`] is an
+//! extractor that validates that header and injects itself into request
+//! extensions, analogous to a `process_auth_header` middleware step.
+//!
+//! The JWT's `sub` claim is the local user id directly (see [`Claims`]),
+//! set once at issuance from the row [`crate::database::users::upsert_from_oauth`]
+//! already resolved. There's deliberately no `(provider, provider_user_id)`
+//! lookup on this path: by the time a request carries a session token, it's
+//! already been mapped to a local account.
+
+use std::env;
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+/// Secret used to sign and verify session tokens.
+static SESSION_SECRET: Lazy<String> = Lazy::new(|| {
+    env::var("SESSION_JWT_SECRET").expect("SESSION_JWT_SECRET must be set")
+});
+
+/// Lifetime of a session token, in seconds. Defaults to 7 days.
+static SESSION_TTL_SECONDS: Lazy<i64> = Lazy::new(|| {
+    env::var("SESSION_TTL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(7 * 24 * 60 * 60)
+});
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// Local user id.
+    sub: i32,
+    exp: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("missing or malformed Authorization header")]
+    MissingCredentials,
+    #[error("invalid or expired session token")]
+    InvalidToken,
+}
+
+impl ResponseError for Error {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::Unauthorized().finish()
+    }
+}
+
+/// Mint a signed session token identifying the local user `user_id`.
+pub fn issue_token(user_id: i32) -> Result<String, Error> {
+    let claims = Claims {
+        sub: user_id,
+        exp: chrono::Utc::now().timestamp() + *SESSION_TTL_SECONDS,
+    };
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(SESSION_SECRET.as_bytes()),
+    )
+    .map_err(|_| Error::InvalidToken)
+}
+
+/// The authenticated local user attached to a request by the `Authorization`
+/// header, once [`AuthenticatedUser::from_request`] has validated its token.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: i32,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = process_auth_header(req);
+        if let Ok(user) = &result {
+            req.extensions_mut().insert(user.clone());
+        }
+        ready(result)
+    }
+}
+
+/// Parse and validate the `Authorization: Bearer <jwt>` header on `req`.
+fn process_auth_header(req: &HttpRequest) -> Result<AuthenticatedUser, Error> {
+    let header = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(Error::MissingCredentials)?;
+
+    let token = header
+        .strip_prefix("Bearer ")
+        .ok_or(Error::MissingCredentials)?;
+
+    let data = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(SESSION_SECRET.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|_| Error::InvalidToken)?;
+
+    Ok(AuthenticatedUser {
+        user_id: data.claims.sub,
+    })
+}